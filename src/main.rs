@@ -1,21 +1,80 @@
 use anyhow::{bail, ensure, Context, Result};
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
 use bdk::{
     bitcoin::{
         self,
         consensus::{deserialize, encode::serialize},
-        util::psbt::PartiallySignedTransaction,
-        Address,
+        hashes::{sha256, Hash},
+        util::{
+            bip32::Fingerprint,
+            psbt::{self, PartiallySignedTransaction},
+        },
+        Address, LockTime, Sequence,
+    },
+    blockchain::{
+        any::{AnyBlockchain, AnyBlockchainConfig},
+        electrum::ElectrumBlockchainConfig,
+        esplora::EsploraBlockchainConfig,
+        noop_progress,
+        rpc::{Auth, RpcConfig},
+        ConfigurableBlockchain,
+    },
+    database::{any::AnyDatabase, BatchDatabase, MemoryDatabase},
+    descriptor::{
+        policy::{PkOrF, Policy, SatisfiableItem},
+        Descriptor,
     },
-    blockchain::{noop_progress, ElectrumBlockchain},
-    database::MemoryDatabase,
-    descriptor::Descriptor,
-    electrum_client::Client,
-    miniscript::DescriptorPublicKey,
-    wallet::{coin_selection::DefaultCoinSelectionAlgorithm, AddressIndex, AddressInfo},
-    SignOptions, Wallet,
+    miniscript::{policy::Concrete, DescriptorPublicKey, Segwitv0},
+    sled,
+    wallet::{coin_selection::DefaultCoinSelectionAlgorithm, AddressIndex, AddressInfo, KeychainKind},
+    FeeRate, SignOptions, Wallet,
 };
+use hwi::{types::HWIChain, HWIClient};
+
+// Which blockchain source `create_wallet` should talk to. Wrapping this (and the database
+// below) behind BDK's `Any*` enums means the rest of the program doesn't care which one is live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Electrum,
+    Esplora,
+    Rpc,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "electrum" => Ok(Backend::Electrum),
+            "esplora" => Ok(Backend::Esplora),
+            "rpc" => Ok(Backend::Rpc),
+            _ => bail!("Unknown backend, expected electrum, esplora or rpc"),
+        }
+    }
+}
+
+// Each backend has its own sane default so `--server` is only needed to override it
+fn default_server(backend: Backend, network: bitcoin::Network) -> String {
+    match backend {
+        Backend::Electrum => "ssl://electrum.blockstream.info:60002".into(),
+        Backend::Esplora => match network {
+            bitcoin::Network::Bitcoin => "https://blockstream.info/api".into(),
+            _ => "https://blockstream.info/testnet/api".into(),
+        },
+        Backend::Rpc => "127.0.0.1:18332".into(),
+    }
+}
+
+// Connection settings shared by every mode, parsed once up front instead of per-subcommand
+#[derive(Debug, Clone)]
+struct Config {
+    network: bitcoin::Network,
+    backend: Backend,
+    server: String,
+    datadir: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 enum Mode {
@@ -26,12 +85,27 @@ enum Mode {
     Receive {
         descriptor: String,
         index: u32,
+        hwi: Option<String>,
     },
     Send {
         descriptor: String,
         change_descriptor: String,
         amount: u64,
         destination: String,
+        recovery: bool,
+        assets: Option<String>,
+        feerate: Option<f32>,
+    },
+    Compile {
+        policy: String,
+        change_policy: Option<String>,
+    },
+    Sign {
+        descriptor: String,
+        psbt: String,
+        // With --hwi, signs on a hardware device. Without it, `descriptor` is treated as a
+        // private-key descriptor and signed in software, e.g. an offline signer wallet.
+        hwi: Option<String>,
     },
     Broadcast {
         descriptor: String,
@@ -39,16 +113,133 @@ enum Mode {
     },
 }
 
+// Find a connected hardware wallet matching `device_type` and open a client for it.
+// `hwi` identifies devices by `device_type` (e.g. "coldcard", "trezor"), not by fingerprint,
+// so this only works when a single matching device is plugged in.
+fn hwi_client(device_type: &str, network: bitcoin::Network) -> Result<HWIClient> {
+    let devices = HWIClient::enumerate()?;
+    let device = devices
+        .into_iter()
+        .find(|d| d.device_type == device_type)
+        .context("Couldn't find a connected device of that type")?;
+
+    // `testnet` and `HWIChain` both need setting since some devices key off one, some the other
+    let (testnet, chain) = match network {
+        bitcoin::Network::Bitcoin => (false, HWIChain::Main),
+        bitcoin::Network::Testnet => (true, HWIChain::Test),
+        bitcoin::Network::Signet => (true, HWIChain::Signet),
+        bitcoin::Network::Regtest => (true, HWIChain::Regtest),
+    };
+
+    HWIClient::get_client(&device, testnet, chain)
+}
+
+// Compile a Miniscript policy (e.g. `or(pk(A),and(pk(B),older(25920)))`) into a `wsh(...)`
+// descriptor. Keeping this separate from `execute` means `compile` can reuse it for the change side.
+fn compile_policy(policy: &str) -> Result<Descriptor<DescriptorPublicKey>> {
+    let policy: Concrete<DescriptorPublicKey> = Concrete::from_str(policy)?;
+    let miniscript = policy.compile::<Segwitv0>()?;
+
+    Ok(Descriptor::new_wsh(miniscript)?)
+}
+
+// Walk a wallet's spending policy looking for a relative-timelock (`older(n)`) branch, and
+// return both the `nSequence` it requires and the `policy_path` selection that picks it.
+// Returns `None` if the descriptor has no timelocked recovery branch to spend through.
+fn find_recovery_path(policy: &Policy) -> Option<(u32, BTreeMap<String, Vec<usize>>)> {
+    match &policy.item {
+        SatisfiableItem::RelativeTimelock { value } => {
+            Some((value.to_consensus_u32(), BTreeMap::new()))
+        }
+        SatisfiableItem::Thresh { items, threshold } => items.iter().enumerate().find_map(|(i, item)| {
+            let (value, mut path) = find_recovery_path(item)?;
+            // A k-of-n node (e.g. the `and` around `older(n)`) needs `threshold` items selected,
+            // so take the timelocked item and fill up with its siblings
+            let mut selected = vec![i];
+            selected.extend((0..items.len()).filter(|&j| j != i).take(threshold - 1));
+            selected.sort_unstable();
+            path.insert(policy.id.clone(), selected);
+            Some((value, path))
+        }),
+        _ => None,
+    }
+}
+
+// The conditions a spender actually has available: which keys they control (by master
+// fingerprint) and which timelocks have matured. Miniscript 10.x has a `plan`/`Assets` API for
+// exactly this, but the miniscript release this project is pinned to doesn't have it yet, so
+// `--assets` walks the descriptor's own policy tree instead (see `is_satisfiable`).
+#[derive(Debug, Clone, Default)]
+struct Assets {
+    keys: Vec<Fingerprint>,
+    after: Option<LockTime>,
+    older: Option<Sequence>,
+}
+
+// Parse `--assets "key:<fingerprint>,after:<height>,older:<blocks>"` into the set of conditions
+// `is_satisfiable` checks a descriptor's policy against.
+fn parse_assets(raw: &str) -> Result<Assets> {
+    let mut assets = Assets::default();
+
+    for entry in raw.split(',') {
+        let (kind, value) = entry
+            .split_once(':')
+            .context("Expected <kind>:<value> in --assets, e.g. key:<fingerprint>")?;
+
+        match kind {
+            "key" => assets.keys.push(Fingerprint::from_str(value)?),
+            "after" => assets.after = Some(LockTime::from_height(value.parse()?)?),
+            "older" => assets.older = Some(Sequence::from_height(value.parse()?)),
+            other => bail!("Unknown --assets kind '{}', expected key/after/older", other),
+        }
+    }
+
+    Ok(assets)
+}
+
+// Whether a single key requirement is covered by the fingerprints in `assets`
+fn pkorf_satisfied(pkorf: &PkOrF, assets: &Assets) -> bool {
+    match pkorf {
+        PkOrF::Fingerprint(fingerprint) => assets.keys.contains(fingerprint),
+        // A bare (no-origin) key can't be matched against a `--assets key:<fingerprint>`
+        // entry, so treat it as unavailable rather than guessing
+        PkOrF::Pubkey(_) | PkOrF::XOnlyPubkey(_) => false,
+    }
+}
+
+// Walk a descriptor's spending policy and check whether `assets` satisfies it, the same way
+// `find_recovery_path` walks it looking for a timelock branch.
+fn is_satisfiable(policy: &Policy, assets: &Assets) -> bool {
+    match &policy.item {
+        SatisfiableItem::Signature(pkorf) | SatisfiableItem::SchnorrSignature(pkorf) => {
+            pkorf_satisfied(pkorf, assets)
+        }
+        SatisfiableItem::Multisig { keys, threshold } => {
+            keys.iter().filter(|k| pkorf_satisfied(k, assets)).count() >= *threshold
+        }
+        SatisfiableItem::AbsoluteTimelock { value } => {
+            assets.after.map_or(false, |after| after >= *value)
+        }
+        SatisfiableItem::RelativeTimelock { value } => {
+            assets.older.map_or(false, |older| older >= *value)
+        }
+        SatisfiableItem::Thresh { items, threshold } => {
+            items.iter().filter(|item| is_satisfiable(item, assets)).count() >= *threshold
+        }
+        _ => false,
+    }
+}
+
 fn main() {
-    let mode = match parse_args() {
-        Ok(m) => m,
+    let cli = match parse_args() {
+        Ok(cli) => cli,
         Err(e) => {
             eprintln!("Error: {}.", e);
             std::process::exit(1);
         }
     };
 
-    match execute(mode) {
+    match execute(cli) {
         Ok(m) => m,
         Err(e) => {
             eprintln!("Error: {}.", e);
@@ -57,20 +248,60 @@ fn main() {
     }
 }
 
-fn parse_args() -> Result<Mode> {
+fn parse_args() -> Result<(Config, Mode)> {
     let mut pargs = pico_args::Arguments::from_env();
     let subcommand = pargs.subcommand()?;
 
     ensure!(
         subcommand.is_some(),
-        "Need to pick a mode: balance || receive || send || broadcast"
+        "Need to pick a mode: balance || receive || send || compile || sign || broadcast"
     );
+    let subcommand = subcommand.unwrap();
+
+    // `compile` doesn't talk to a wallet, so it takes a policy string instead of a descriptor
+    // and doesn't need any of the shared connection settings below
+    if subcommand == "compile" {
+        let policy: String = pargs
+            .free_from_str()
+            .context("Need to include a miniscript policy")?;
+        let change_policy: Option<String> = pargs.opt_value_from_str("--change-policy")?;
+
+        // `compile` never opens a wallet, so the connection settings are left at their defaults
+        let config = Config {
+            network: bitcoin::Network::Testnet,
+            backend: Backend::Electrum,
+            server: default_server(Backend::Electrum, bitcoin::Network::Testnet),
+            datadir: None,
+        };
+
+        return Ok((config, Mode::Compile { policy, change_policy }));
+    }
 
     let descriptor: String = pargs
         .free_from_str()
         .context("Need to include a descriptor")?;
 
-    let info = match subcommand.unwrap().as_str() {
+    // Shared connection settings. These can be passed alongside any subcommand but `compile`, e.g.
+    // `receive <desc> --index 0 --network signet --backend esplora --datadir ./wallet-db`
+    let network: bitcoin::Network = pargs
+        .opt_value_from_str("--network")?
+        .unwrap_or(bitcoin::Network::Testnet);
+    let backend: Backend = pargs
+        .opt_value_from_str("--backend")?
+        .unwrap_or(Backend::Electrum);
+    let server: String = pargs
+        .opt_value_from_str("--server")?
+        .unwrap_or_else(|| default_server(backend, network));
+    let datadir: Option<String> = pargs.opt_value_from_str("--datadir")?;
+
+    let config = Config {
+        network,
+        backend,
+        server,
+        datadir,
+    };
+
+    let info = match subcommand.as_str() {
         "balance" => Mode::Balance {
             descriptor,
             change_descriptor: pargs
@@ -82,6 +313,8 @@ fn parse_args() -> Result<Mode> {
             index: pargs
                 .value_from_str("--index")
                 .context("Missing index argument")?,
+            // Optional: pass e.g. --hwi "coldcard" to confirm the derived address on-device
+            hwi: pargs.opt_value_from_str("--hwi")?,
         },
         "send" => Mode::Send {
             descriptor,
@@ -92,6 +325,19 @@ fn parse_args() -> Result<Mode> {
             destination: pargs
                 .value_from_str("--dest")
                 .context("Missing destination address")?,
+            // Spend via the descriptor's timelocked recovery branch instead of the primary key
+            recovery: pargs.contains("--recovery"),
+            // e.g. --assets "key:<fingerprint>,after:<height>" — see `parse_assets`
+            assets: pargs.opt_value_from_str("--assets")?,
+            // Target fee rate in sat/vB. Without it, BDK falls back to its own default
+            feerate: pargs.opt_value_from_str("--feerate")?,
+        },
+        "sign" => Mode::Sign {
+            descriptor,
+            psbt: pargs.value_from_str("--psbt").context("Missing PSBT")?,
+            // Optional: pass e.g. --hwi "coldcard" to sign on a hardware device instead of
+            // in software with the private-key descriptor given above
+            hwi: pargs.opt_value_from_str("--hwi")?,
         },
         "broadcast" => Mode::Broadcast {
             descriptor,
@@ -100,16 +346,56 @@ fn parse_args() -> Result<Mode> {
         _ => bail!("Unknown mode"),
     };
 
-    Ok(info)
+    Ok((config, info))
+}
+
+// With `--datadir` we open a sled tree so wallet state (and sync progress) persists across
+// runs; without it we fall back to the original ephemeral in-memory database.
+fn open_database(config: &Config, desc_string: &str) -> Result<AnyDatabase> {
+    match &config.datadir {
+        Some(datadir) => {
+            let tree = sled::open(datadir)?.open_tree(desc_string)?;
+            Ok(AnyDatabase::Sled(tree))
+        }
+        None => Ok(AnyDatabase::Memory(MemoryDatabase::default())),
+    }
+}
+
+// Bitcoind's RPC wallet name becomes a path on disk, so a raw descriptor string (full of
+// `()[]/'*,` characters) isn't safe to hand it over directly — hash it into something
+// filesystem- and RPC-safe instead.
+fn rpc_wallet_name(desc_string: &str) -> String {
+    sha256::Hash::hash(desc_string.as_bytes()).to_string()
 }
 
-// Hardcoded blockchain and database types. Could also use AnyBlockchain / AnyDatabase to allow switching.
 fn create_wallet(
+    config: &Config,
     desc_string: &str,
     change_desc: Option<&str>,
-) -> Result<Wallet<ElectrumBlockchain, MemoryDatabase>> {
-    // Create a SSL-encrypted Electrum client
-    let client = Client::new("ssl://electrum.blockstream.info:60002")?;
+) -> Result<Wallet<AnyBlockchain, AnyDatabase>> {
+    // Build whichever backend the user asked for behind the `AnyBlockchain` enum, so the rest
+    // of the program doesn't need to know if it's talking to Electrum, Esplora or bitcoind
+    let blockchain_config = match config.backend {
+        Backend::Electrum => AnyBlockchainConfig::Electrum(ElectrumBlockchainConfig {
+            url: config.server.clone(),
+            socks5: None,
+            retry: 3,
+            timeout: None,
+            stop_gap: 10,
+        }),
+        Backend::Esplora => {
+            AnyBlockchainConfig::Esplora(EsploraBlockchainConfig::new(config.server.clone(), 20))
+        }
+        Backend::Rpc => AnyBlockchainConfig::Rpc(RpcConfig {
+            url: config.server.clone(),
+            auth: Auth::None,
+            network: config.network,
+            wallet_name: rpc_wallet_name(desc_string),
+            skip_blocks: None,
+        }),
+    };
+    let blockchain = AnyBlockchain::from_config(&blockchain_config)?;
+    let database = open_database(config, desc_string)?;
 
     // Create a BDK wallet
     let wallet = Wallet::new(
@@ -117,31 +403,37 @@ fn create_wallet(
         desc_string,
         // Descriptor used for generating change addresses
         change_desc,
-        // Which network we'll using. If you change this to `Bitcoin` things get real.
-        bitcoin::Network::Testnet,
-        // In-memory ephemeral database. There's also a default key value storage provided by BDK if you want persistence.
-        MemoryDatabase::default(),
-        // This wrapper implements the blockchain traits BDK needs for this specific client type
-        ElectrumBlockchain::from(client),
+        // Which network we're using. If you change this to `Bitcoin` things get real.
+        config.network,
+        database,
+        blockchain,
     )?;
 
     println!("Syncing...");
 
-    // Important! We have to sync our wallet with the blockchain.
-    // Because our wallet is ephemeral we need to do this on each run, so I put it in `create_wallet` for convenience.
+    // Important! We have to sync our wallet with the blockchain. With a persistent datadir
+    // this is incremental; with the in-memory database it's a full rescan every run.
     wallet.sync(noop_progress(), None)?;
 
     Ok(wallet)
 }
 
-fn execute(mode: Mode) -> Result<()> {
+// For modes that only sign with a known descriptor (no UTXO lookups, no broadcasting), we don't
+// want to touch the network at all — a genuinely air-gapped signer has no server to reach.
+fn create_offline_wallet(config: &Config, desc_string: &str) -> Result<Wallet<(), AnyDatabase>> {
+    let database = open_database(config, desc_string)?;
+
+    Ok(Wallet::new(desc_string, None, config.network, database, ())?)
+}
+
+fn execute((config, mode): (Config, Mode)) -> Result<()> {
     match mode {
         Mode::Balance {
             descriptor,
             change_descriptor,
         } => {
             // We need to include the change descriptor to correctly calculate the balance, in case it's holding some of our sats
-            let wallet = create_wallet(&descriptor, Some(&change_descriptor))?;
+            let wallet = create_wallet(&config, &descriptor, Some(&change_descriptor))?;
 
             // Get the balance in sats
             // It's a sum of the unspent outputs known to the wallet's internal database (so you need to sync first)
@@ -153,8 +445,12 @@ fn execute(mode: Mode) -> Result<()> {
 
             Ok(())
         }
-        Mode::Receive { descriptor, index } => {
-            let wallet = create_wallet(&descriptor, None)?;
+        Mode::Receive {
+            descriptor,
+            index,
+            hwi,
+        } => {
+            let wallet = create_wallet(&config, &descriptor, None)?;
 
             // Derives an address based on the wallet's descriptor and the given index
             let info = wallet.get_address(AddressIndex::Peek(index))?;
@@ -172,13 +468,22 @@ fn execute(mode: Mode) -> Result<()> {
             // We can use this with hwi's `displayaddress` method
             let desc: Descriptor<DescriptorPublicKey> = underived_desc.derive(index);
 
-            // We could use rust-hwi to verify this address from within our "app"
-            // But let's just do it manually for now
-            // hwi -t "coldcard" displayaddress --desc "..."
             println!("derived descriptor: {}", desc);
             println!("index: {}", index);
             println!("address: {}", address);
 
+            // If a device type was given, ask the hardware wallet to display the address
+            // derived from this exact descriptor so the user can confirm it on-device
+            if let Some(device_type) = hwi {
+                let client = hwi_client(&device_type, config.network)?;
+
+                println!("Confirming address on {}...", device_type);
+                client
+                    .display_address_with_desc(&desc)
+                    .or_else(|_| client.display_address_with_path(&underived_desc, &[index]))
+                    .context("Device rejected or failed to display the address")?;
+            }
+
             Ok(())
         }
         Mode::Send {
@@ -186,8 +491,11 @@ fn execute(mode: Mode) -> Result<()> {
             change_descriptor,
             amount,
             destination,
+            recovery,
+            assets,
+            feerate,
         } => {
-            let wallet = create_wallet(&descriptor, Some(&change_descriptor))?;
+            let wallet = create_wallet(&config, &descriptor, Some(&change_descriptor))?;
 
             // Use rust-bitcoin to parse the address string into its `Address` type
             // Then convert this address into a script pubkey that spends to it
@@ -204,12 +512,80 @@ fn execute(mode: Mode) -> Result<()> {
             // The Coldcard requires an output redeem witness script
             tx_builder.include_output_redeem_witness_script();
 
-            // Enable signaling replace-by-fee
-            tx_builder.enable_rbf();
+            if let Some(assets) = assets {
+                // With --assets we check up front whether the spender's available keys and
+                // matured timelocks actually satisfy this descriptor, instead of trusting the
+                // default coin selection to know what this wallet can sign for
+                let assets = parse_assets(&assets)?;
+                let policy = wallet
+                    .policies(KeychainKind::External)?
+                    .context("This descriptor has no spending policy")?;
+
+                ensure!(
+                    is_satisfiable(&policy, &assets),
+                    "This descriptor isn't satisfiable with the given --assets"
+                );
+
+                let underived: Descriptor<DescriptorPublicKey> =
+                    bdk::miniscript::Descriptor::from_str(&descriptor)?;
+
+                tx_builder.manually_selected_only();
+
+                for utxo in wallet.list_unspent()? {
+                    let (_, child) = wallet
+                        .database()
+                        .get_path_from_script_pubkey(&utxo.txout.script_pubkey)?
+                        .context("Unknown derivation path for this UTXO")?;
+                    let definite_desc = underived.derive(child);
+                    let satisfaction_weight = definite_desc.max_satisfaction_weight()?;
+
+                    println!(
+                        "{}: satisfiable, satisfaction weight {}",
+                        utxo.outpoint, satisfaction_weight
+                    );
+
+                    // These UTXOs are our own, not actually foreign, so `add_foreign_utxo` won't
+                    // fill in the witness script for us the way normal coin selection does —
+                    // pull it from the definite descriptor ourselves, or the signer has nothing
+                    // to build a witness from.
+                    let psbt_input = psbt::Input {
+                        witness_utxo: Some(utxo.txout.clone()),
+                        witness_script: Some(definite_desc.explicit_script()?),
+                        ..Default::default()
+                    };
+
+                    tx_builder.add_foreign_utxo(utxo.outpoint, psbt_input, satisfaction_weight)?;
+                }
+            }
+
+            if recovery {
+                // With --recovery we're not spending via the primary key, so walk the
+                // descriptor's policy tree for the `older(n)` branch instead
+                let policy = wallet
+                    .policies(KeychainKind::External)?
+                    .context("This descriptor has no spending policy")?;
+                let (csv, path) = find_recovery_path(&policy)
+                    .context("This descriptor has no timelocked recovery branch")?;
+
+                println!("Spending via the older({}) recovery branch", csv);
+
+                // Select the recovery branch and signal the nSequence it requires, since a
+                // relative timelock is enforced through the input's sequence number
+                tx_builder.policy_path(path, KeychainKind::External);
+                tx_builder.enable_rbf_with_sequence(Sequence::from_consensus(csv));
+            } else {
+                // Enable signaling replace-by-fee
+                tx_builder.enable_rbf();
+            }
 
             // Add our script and the amount in sats to send
             tx_builder.add_recipient(dest_script, amount);
 
+            // Without --feerate BDK falls back to its own default fee rate
+            if let Some(feerate) = feerate {
+                tx_builder.fee_rate(FeeRate::from_sat_per_vb(feerate));
+            }
+
             // "Finish" the builder which returns a tuple:
             // A `PartiallySignedTransaction` which serializes as a psbt
             // And `TransactionDetails` which has helpful info about the transaction we just built
@@ -219,8 +595,65 @@ fn execute(mode: Mode) -> Result<()> {
 
             Ok(())
         }
+        Mode::Compile {
+            policy,
+            change_policy,
+        } => {
+            let descriptor = compile_policy(&policy)?;
+            println!("descriptor: {}", descriptor);
+
+            // The change variant comes from its own policy (e.g. the same keys but a
+            // different derivation path), the same way `--change` works for `balance`/`send`
+            if let Some(change_policy) = change_policy {
+                let change_descriptor = compile_policy(&change_policy)?;
+                println!("change descriptor: {}", change_descriptor);
+            }
+
+            Ok(())
+        }
+        Mode::Sign {
+            descriptor,
+            psbt,
+            hwi,
+        } => {
+            // Deserialize the unsigned psbt the same way `broadcast` does
+            let psbt_bytes = base64::decode(&psbt)?;
+            let mut psbt: PartiallySignedTransaction = deserialize(&psbt_bytes)?;
+
+            match hwi {
+                Some(device_type) => {
+                    // `descriptor` here is the watch-only descriptor, used only to finalize
+                    let wallet = create_wallet(&config, &descriptor, None)?;
+                    let client = hwi_client(&device_type, config.network)?;
+
+                    println!("Please confirm the transaction on {}...", device_type);
+
+                    // Send the psbt to the hardware wallet and get back its signed version
+                    psbt = client.sign_tx(&psbt)?.psbt;
+
+                    // Finalize and extract the transaction exactly like `broadcast` does, then
+                    // hand the finished, signed psbt back so it can be fed into `broadcast`
+                    let sign_options = SignOptions::default();
+                    let _psbt_is_finalized = wallet.finalize_psbt(&mut psbt, sign_options)?;
+                    let _tx = psbt.clone().extract_tx();
+                }
+                None => {
+                    // Here `descriptor` holds the private keys: this is the offline signer
+                    // half of the watch-only/signer split, so we sign but don't broadcast — that's
+                    // left to the watch-only wallet via `broadcast`. With the default sign options
+                    // BDK also finalizes any input these signatures complete. Signing
+                    // never needs a blockchain client, so this never touches the network.
+                    let signer_wallet = create_offline_wallet(&config, &descriptor)?;
+                    signer_wallet.sign(&mut psbt, SignOptions::default())?;
+                }
+            }
+
+            println!("{}", base64::encode(&serialize(&psbt)));
+
+            Ok(())
+        }
         Mode::Broadcast { descriptor, psbt } => {
-            let wallet = create_wallet(&descriptor, None)?;
+            let wallet = create_wallet(&config, &descriptor, None)?;
 
             // Deserialize the psbt. First as a Vec of bytes, then as a strongly typed `PartiallySignedTransaction`
             let psbt = base64::decode(&psbt)?;